@@ -1,10 +1,9 @@
 use super::model::Capturer;
-use crate::duplication_context::DuplicationContext;
-use crate::utils::OutDuplDescExt;
-use crate::Error;
+use crate::duplicate_context::DuplicateContext;
+use crate::utils::{composite_cursor, OutputDescExt};
 use crate::Result;
-use windows::Win32::Graphics::Direct3D11::D3D11_TEXTURE2D_DESC;
-use windows::Win32::Graphics::Dxgi::DXGI_OUTDUPL_POINTER_SHAPE_INFO;
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Dxgi::{DXGI_OUTDUPL_MOVE_RECT, DXGI_OUTDUPL_POINTER_SHAPE_INFO};
 use windows::Win32::Graphics::{
   Direct3D11::ID3D11Texture2D,
   Dxgi::{DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC},
@@ -13,43 +12,142 @@ use windows::Win32::Graphics::{
 /// Capture screen to a chunk of memory.
 pub struct CustomCapturer<'a> {
   buffer: &'a mut [u8],
-  ctx: &'a DuplicationContext,
+  ctx: &'a DuplicateContext,
   texture: ID3D11Texture2D,
-  texture_desc: D3D11_TEXTURE2D_DESC,
   pointer_shape_buffer: Vec<u8>,
   pointer_shape_buffer_size: usize,
+  cached_pointer_shape_info: Option<DXGI_OUTDUPL_POINTER_SHAPE_INFO>,
+  metadata_buffer: Vec<u8>,
+  move_rect_count: usize,
+  dirty_rect_count: usize,
+  has_previous_frame: bool,
 }
 
 impl<'a> CustomCapturer<'a> {
-  pub fn with_texture(
-    ctx: &'a DuplicationContext,
-    buffer: &'a mut [u8],
-    texture: ID3D11Texture2D,
-    texture_desc: D3D11_TEXTURE2D_DESC,
-  ) -> Self {
+  pub fn with_texture(ctx: &'a DuplicateContext, buffer: &'a mut [u8], texture: ID3D11Texture2D) -> Self {
     Self {
       buffer,
       ctx,
       texture,
-      texture_desc,
       pointer_shape_buffer: Vec::new(),
       pointer_shape_buffer_size: 0,
+      cached_pointer_shape_info: None,
+      metadata_buffer: Vec::new(),
+      move_rect_count: 0,
+      dirty_rect_count: 0,
+      has_previous_frame: false,
     }
   }
 
-  pub fn new(ctx: &'a DuplicationContext, buffer: &'a mut [u8]) -> Result<Self> {
-    let (texture, _desc, texture_desc) = ctx.create_readable_texture()?;
-    Ok(Self::with_texture(ctx, buffer, texture, texture_desc))
+  pub fn new(ctx: &'a DuplicateContext, buffer: &'a mut [u8]) -> Result<Self> {
+    let texture = ctx.create_readable_texture()?;
+    Ok(Self::with_texture(ctx, buffer, texture))
+  }
+
+  /// Move rectangles from the most recent `capture_with_metadata` or `capture_incremental`
+  /// call (empty when `capture_incremental` fell back to a full copy).
+  pub fn move_rects(&self) -> &[DXGI_OUTDUPL_MOVE_RECT] {
+    let ptr = self.metadata_buffer.as_ptr() as *const DXGI_OUTDUPL_MOVE_RECT;
+    unsafe { std::slice::from_raw_parts(ptr, self.move_rect_count) }
+  }
+
+  /// Dirty rectangles from the most recent `capture_with_metadata` or `capture_incremental`
+  /// call (empty when `capture_incremental` fell back to a full copy).
+  pub fn dirty_rects(&self) -> &[RECT] {
+    let offset = self.move_rect_count * std::mem::size_of::<DXGI_OUTDUPL_MOVE_RECT>();
+    let ptr = self.metadata_buffer[offset..].as_ptr() as *const RECT;
+    unsafe { std::slice::from_raw_parts(ptr, self.dirty_rect_count) }
+  }
+
+  /// Like [`Capturer::capture`] but also populates [`Self::move_rects`] and
+  /// [`Self::dirty_rects`] from the frame's change metadata, when DXGI reports any.
+  pub fn capture_with_metadata(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let frame_info = self.capture()?;
+    (self.move_rect_count, self.dirty_rect_count) = self
+      .ctx
+      .frame_metadata(&frame_info, &mut self.metadata_buffer)?;
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_with_metadata(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_with_metadata()
+  }
+
+  /// Like [`Capturer::capture`], but rewrites the captured frame so it is always upright,
+  /// regardless of the output's rotation. An unrotated frame is copied with one memcpy like
+  /// `capture`; a rotated frame is transformed pixel by pixel.
+  pub fn capture_upright(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.ctx.capture_frame_upright(self.buffer.as_mut_ptr(), &self.texture)
+  }
+
+  pub fn safe_capture_upright(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_upright()
+  }
+
+  /// Like [`Capturer::capture`], but draws the hardware cursor onto the captured frame using
+  /// [`crate::utils::composite_cursor`]. The pointer shape is only re-fetched when
+  /// `frame_info.mouse_updated()` reports a change; otherwise the last cached shape is reused.
+  pub fn capture_with_cursor(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let (frame_info, pointer_shape_info) = self.capture_with_pointer_shape()?;
+    if pointer_shape_info.is_some() {
+      self.cached_pointer_shape_info = pointer_shape_info;
+    }
+
+    if frame_info.PointerPosition.Visible.as_bool() {
+      if let Some(shape) = &self.cached_pointer_shape_info {
+        let desc = self.dxgi_output_desc()?;
+        composite_cursor(
+          self.buffer,
+          desc.width(),
+          desc.height(),
+          frame_info.PointerPosition.Position,
+          shape,
+          &self.pointer_shape_buffer[..self.pointer_shape_buffer_size],
+        );
+      }
+    }
+
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_with_cursor(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_with_cursor()
+  }
+
+  /// Like [`Capturer::capture`], but only transfers the regions DXGI reports as changed once
+  /// [`Self::buffer`] already holds a previous frame, patching them in place instead of
+  /// re-copying the whole desktop. Also refreshes [`Self::move_rects`]/[`Self::dirty_rects`].
+  /// Falls back to a full copy on the first call, and again on the call right after the
+  /// duplication session had to be rebuilt, since `buffer` can no longer be trusted to match.
+  pub fn capture_incremental(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let desc = self.dxgi_output_desc()?;
+    let (frame_info, move_rect_count, dirty_rect_count, reacquired) =
+      self.ctx.capture_frame_incremental(
+        self.buffer.as_mut_ptr(),
+        self.buffer.len(),
+        desc.width(),
+        &self.texture,
+        &mut self.metadata_buffer,
+        self.has_previous_frame,
+      )?;
+    self.move_rect_count = move_rect_count;
+    self.dirty_rect_count = dirty_rect_count;
+    self.has_previous_frame = !reacquired;
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_incremental(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_incremental()
   }
 }
 
 impl Capturer for CustomCapturer<'_> {
   fn dxgi_output_desc(&self) -> Result<DXGI_OUTPUT_DESC> {
-    self.ctx.dxgi_output_desc()
-  }
-
-  fn dxgi_outdupl_desc(&self) -> windows::Win32::Graphics::Dxgi::DXGI_OUTDUPL_DESC {
-    self.ctx.dxgi_outdupl_desc()
+    self.ctx.get_desc()
   }
 
   fn buffer(&self) -> &[u8] {
@@ -61,8 +159,9 @@ impl Capturer for CustomCapturer<'_> {
   }
 
   fn check_buffer(&self) -> Result<()> {
-    if self.buffer.len() < self.dxgi_outdupl_desc().calc_buffer_size() {
-      Err(Error::InvalidBufferLength)
+    let desc = self.dxgi_output_desc()?;
+    if self.buffer.len() < desc.calc_buffer_size() {
+      Err("Invalid buffer length".into())
     } else {
       Ok(())
     }
@@ -73,12 +172,9 @@ impl Capturer for CustomCapturer<'_> {
   }
 
   fn capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
-    self.ctx.capture(
-      self.buffer.as_mut_ptr(),
-      self.buffer.len(),
-      &self.texture,
-      &self.texture_desc,
-    )
+    self
+      .ctx
+      .capture_frame(self.buffer.as_mut_ptr(), self.buffer.len(), &self.texture)
   }
 
   fn safe_capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
@@ -96,7 +192,6 @@ impl Capturer for CustomCapturer<'_> {
       self.buffer.as_mut_ptr(),
       self.buffer.len(),
       &self.texture,
-      &self.texture_desc,
       &mut self.pointer_shape_buffer,
     )?;
 
@@ -119,7 +214,7 @@ impl Capturer for CustomCapturer<'_> {
   }
 }
 
-impl DuplicationContext {
+impl DuplicateContext {
   pub fn custom_capturer<'a>(&'a self, buffer: &'a mut [u8]) -> Result<CustomCapturer<'a>> {
     CustomCapturer::<'a>::new(self, buffer)
   }
@@ -130,7 +225,7 @@ mod tests {
   use crate::{
     capturer::model::Capturer,
     manager::Manager,
-    utils::{FrameInfoExt, OutDuplDescExt},
+    utils::{FrameInfoExt, OutputDescExt},
   };
   use serial_test::serial;
   use std::{thread, time::Duration};
@@ -138,12 +233,11 @@ mod tests {
   #[test]
   #[serial]
   fn custom_capturer() {
-    let mut manager = Manager::default();
-    manager.refresh().unwrap();
+    let manager = Manager::new().unwrap();
     assert_ne!(manager.contexts.len(), 0);
 
     let ctx = &manager.contexts[0];
-    let desc = ctx.dxgi_outdupl_desc();
+    let desc = ctx.get_desc().unwrap();
     let mut buffer = vec![0u8; desc.calc_buffer_size()];
     let mut capturer = ctx.custom_capturer(&mut buffer).unwrap();
 
@@ -164,12 +258,16 @@ mod tests {
     }
     assert!(!all_zero);
 
+    // capture_upright should succeed regardless of the output's rotation, and write into the
+    // same buffer size as a regular capture (width*height*4 either way).
+    capturer.safe_capture_upright().unwrap();
+
     // sleep for a while before capture to wait system to update the mouse
     thread::sleep(Duration::from_millis(1000));
 
     // check pointer shape
     let (frame_info, pointer_shape_info) = capturer.safe_capture_with_pointer_shape().unwrap();
-    assert!(frame_info.mouse_updated().position_updated);
+    assert!(frame_info.mouse_updated());
     assert!(pointer_shape_info.is_some());
     let pointer_shape_data = capturer.pointer_shape_buffer();
     // make sure pointer shape buffer is not all zero
@@ -181,5 +279,15 @@ mod tests {
       }
     }
     assert!(!all_zero);
+
+    // capture_with_cursor should composite the cached pointer shape onto the buffer without
+    // erroring, whether or not this particular frame reports a new shape.
+    capturer.safe_capture_with_cursor().unwrap();
+
+    // first call has no previous frame to diff against, so it falls back to a full copy
+    capturer.safe_capture_incremental().unwrap();
+    thread::sleep(Duration::from_millis(100));
+    // second call can actually patch in move/dirty rects
+    capturer.safe_capture_incremental().unwrap();
   }
 }
@@ -0,0 +1,9 @@
+pub mod custom;
+pub mod model;
+pub mod shared;
+pub mod simple;
+
+pub use custom::CustomCapturer;
+pub use model::Capturer;
+pub use shared::SharedTextureCapturer;
+pub use simple::SimpleCapturer;
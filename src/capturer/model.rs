@@ -0,0 +1,43 @@
+use windows::Win32::Graphics::Dxgi::{
+  DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO, DXGI_OUTPUT_DESC,
+};
+
+use crate::model::Result;
+
+/// Common surface shared by [`super::SimpleCapturer`] and [`super::CustomCapturer`]: both hold a
+/// CPU-readable buffer sized for a [`crate::DuplicateContext`]'s output and copy the next
+/// duplicated frame into it.
+pub trait Capturer {
+  /// The output this capturer is reading from.
+  fn dxgi_output_desc(&self) -> Result<DXGI_OUTPUT_DESC>;
+
+  fn buffer(&self) -> &[u8];
+  fn buffer_mut(&mut self) -> &mut [u8];
+
+  /// Check that [`Self::buffer`] is still large enough for the current output.
+  fn check_buffer(&self) -> Result<()>;
+
+  fn pointer_shape_buffer(&self) -> &[u8];
+
+  fn capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO>;
+
+  /// Like [`Self::capture`], but calls [`Self::check_buffer`] first.
+  fn safe_capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO>;
+
+  #[allow(clippy::type_complexity)]
+  fn capture_with_pointer_shape(
+    &mut self,
+  ) -> Result<(
+    DXGI_OUTDUPL_FRAME_INFO,
+    Option<DXGI_OUTDUPL_POINTER_SHAPE_INFO>,
+  )>;
+
+  /// Like [`Self::capture_with_pointer_shape`], but calls [`Self::check_buffer`] first.
+  #[allow(clippy::type_complexity)]
+  fn safe_capture_with_pointer_shape(
+    &mut self,
+  ) -> Result<(
+    DXGI_OUTDUPL_FRAME_INFO,
+    Option<DXGI_OUTDUPL_POINTER_SHAPE_INFO>,
+  )>;
+}
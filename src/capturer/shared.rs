@@ -0,0 +1,111 @@
+use crate::duplicate_context::DuplicateContext;
+use crate::model::{Error, Result};
+use crate::utils::OutputDescExt;
+use windows::core::ComInterface;
+use windows::Win32::Foundation::HANDLE;
+use windows::Win32::Graphics::Direct3D11::{
+  ID3D11Device, ID3D11Texture2D, D3D11_BIND_FLAG, D3D11_RESOURCE_MISC_SHARED_KEYED_MUTEX,
+  D3D11_TEXTURE2D_DESC, D3D11_USAGE_DEFAULT,
+};
+use windows::Win32::Graphics::Dxgi::{
+  Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_SAMPLE_DESC},
+  IDXGIKeyedMutex, IDXGIResource1, DXGI_SHARED_RESOURCE_READ, DXGI_SHARED_RESOURCE_WRITE,
+};
+
+/// Keeps the captured frame on the GPU instead of reading it back to a CPU `Vec<u8>`. Meant
+/// for consumers that hand the frame straight to a GPU encoder (NVENC/QuickSync) or another
+/// D3D11/D3D12 device: they open [`Self::shared_handle`] on their own device and use
+/// [`Self::acquire_sync`]/[`Self::release_sync`] to coordinate access with this capturer,
+/// instead of a busy-wait fence.
+pub struct SharedTextureCapturer<'a> {
+  ctx: &'a DuplicateContext,
+  texture: ID3D11Texture2D,
+  keyed_mutex: IDXGIKeyedMutex,
+}
+
+impl<'a> SharedTextureCapturer<'a> {
+  pub fn new(ctx: &'a DuplicateContext) -> Result<Self> {
+    let desc = ctx.get_desc()?;
+
+    let texture_desc = D3D11_TEXTURE2D_DESC {
+      BindFlags: D3D11_BIND_FLAG::default(),
+      CPUAccessFlags: Default::default(),
+      MiscFlags: D3D11_RESOURCE_MISC_SHARED_KEYED_MUTEX,
+      Usage: D3D11_USAGE_DEFAULT,
+      Width: desc.width(),
+      Height: desc.height(),
+      MipLevels: 1,
+      ArraySize: 1,
+      Format: DXGI_FORMAT_B8G8R8A8_UNORM,
+      SampleDesc: DXGI_SAMPLE_DESC {
+        Count: 1,
+        Quality: 0,
+      },
+    };
+
+    let texture = unsafe {
+      let mut texture: Option<ID3D11Texture2D> = None;
+      ctx
+        .device()
+        .CreateTexture2D(&texture_desc, None, Some(&mut texture))?;
+      texture.unwrap()
+    };
+    let keyed_mutex: IDXGIKeyedMutex = texture.cast()?;
+
+    Ok(Self {
+      ctx,
+      texture,
+      keyed_mutex,
+    })
+  }
+
+  /// The device the shared texture was created on, for callers that want to build their own
+  /// interop on top of it.
+  pub fn device(&self) -> &ID3D11Device {
+    self.ctx.device()
+  }
+
+  /// The GPU texture backing this capturer.
+  pub fn texture(&self) -> &ID3D11Texture2D {
+    &self.texture
+  }
+
+  /// Copy the next duplicated frame into the shared texture. Holds the keyed mutex for the
+  /// duration of the copy so a consumer calling [`Self::acquire_sync`] never observes a
+  /// half-written frame.
+  pub fn capture(&self) -> Result<windows::Win32::Graphics::Dxgi::DXGI_OUTDUPL_FRAME_INFO> {
+    self.acquire_sync(0, u32::MAX)?;
+    let result = self.ctx.capture_into(&self.texture);
+    self.release_sync(0)?;
+    result
+  }
+
+  /// Open a handle to the shared texture that another device can pass to
+  /// `OpenSharedResource`/`OpenSharedResource1` to interop with this capturer.
+  pub fn shared_handle(&self) -> Result<HANDLE> {
+    unsafe {
+      let resource: IDXGIResource1 = self.texture.cast()?;
+      Ok(resource.CreateSharedHandle(
+        None,
+        (DXGI_SHARED_RESOURCE_READ.0 | DXGI_SHARED_RESOURCE_WRITE.0) as u32,
+        windows::core::PCWSTR::null(),
+      )?)
+    }
+  }
+
+  /// Block until `key` is free, then take ownership of the shared texture for this side.
+  pub fn acquire_sync(&self, key: u64, timeout_ms: u32) -> Result<()> {
+    unsafe { self.keyed_mutex.AcquireSync(key, timeout_ms) }.map_err(Error::from)
+  }
+
+  /// Hand ownership of the shared texture back, unblocking whoever is waiting on `key`.
+  pub fn release_sync(&self, key: u64) -> Result<()> {
+    unsafe { self.keyed_mutex.ReleaseSync(key) }.map_err(Error::from)
+  }
+}
+
+impl DuplicateContext {
+  pub fn shared_texture_capturer(&self) -> Result<SharedTextureCapturer> {
+    SharedTextureCapturer::new(self)
+  }
+}
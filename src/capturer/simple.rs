@@ -1,23 +1,31 @@
 use super::model::Capturer;
 use crate::model::Result;
-use crate::utils::FrameInfoExt;
-use crate::{duplication_context::DuplicationContext, utils::OutputDescExt};
-use windows::Win32::Graphics::Dxgi::{DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO};
+use crate::utils::{composite_cursor, FrameInfoExt};
+use crate::{duplicate_context::DuplicateContext, utils::OutputDescExt};
+use windows::Win32::Foundation::RECT;
+use windows::Win32::Graphics::Dxgi::{
+  DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_MOVE_RECT, DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+};
 use windows::Win32::Graphics::{Direct3D11::ID3D11Texture2D, Dxgi::DXGI_OUTPUT_DESC};
 
 /// Capture screen to a `Vec<u8>`.
 pub struct SimpleCapturer<'a> {
   buffer: Vec<u8>,
-  ctx: &'a DuplicationContext,
+  ctx: &'a DuplicateContext,
   texture: ID3D11Texture2D,
   last_pointer_shape_buffer: Vec<u8>,
   last_pointer_shape_buffer_size: usize,
   pointer_shape_buffer: Vec<u8>,
   pointer_shape_buffer_size: usize,
+  cached_pointer_shape_info: Option<DXGI_OUTDUPL_POINTER_SHAPE_INFO>,
+  metadata_buffer: Vec<u8>,
+  move_rect_count: usize,
+  dirty_rect_count: usize,
+  has_previous_frame: bool,
 }
 
 impl<'a> SimpleCapturer<'a> {
-  pub fn new(ctx: &'a DuplicationContext) -> Result<Self> {
+  pub fn new(ctx: &'a DuplicateContext) -> Result<Self> {
     let (buffer, texture) = Self::allocate(ctx)?;
     Ok(Self {
       buffer,
@@ -27,28 +35,136 @@ impl<'a> SimpleCapturer<'a> {
       last_pointer_shape_buffer_size: 0,
       pointer_shape_buffer: Vec::new(),
       pointer_shape_buffer_size: 0,
+      cached_pointer_shape_info: None,
+      metadata_buffer: Vec::new(),
+      move_rect_count: 0,
+      dirty_rect_count: 0,
+      has_previous_frame: false,
     })
   }
 
-  fn allocate(ctx: &'a DuplicationContext) -> Result<(Vec<u8>, ID3D11Texture2D)> {
-    let (texture, desc) = ctx.create_readable_texture()?;
-    let dpi = ctx.effective_dpi(&desc)?;
-    let buffer = vec![0u8; desc.calc_buffer_size(dpi)];
-    println!("dimension: {}x{}", desc.width(), desc.height());
-    println!("dpi: {:?}", dpi);
-    println!(
-      "pixel dimension: {}x{}",
-      desc.pixel_width(dpi.0),
-      desc.pixel_height(dpi.1)
-    );
-    println!("buffer size: {}", buffer.len());
+  fn allocate(ctx: &'a DuplicateContext) -> Result<(Vec<u8>, ID3D11Texture2D)> {
+    let texture = ctx.create_readable_texture()?;
+    let desc = ctx.get_desc()?;
+    let buffer = vec![0u8; desc.calc_buffer_size()];
     Ok((buffer, texture))
   }
+
+  /// Move rectangles from the most recent `capture_with_metadata` or `capture_incremental`
+  /// call (empty when `capture_incremental` fell back to a full copy).
+  pub fn move_rects(&self) -> &[DXGI_OUTDUPL_MOVE_RECT] {
+    let ptr = self.metadata_buffer.as_ptr() as *const DXGI_OUTDUPL_MOVE_RECT;
+    unsafe { std::slice::from_raw_parts(ptr, self.move_rect_count) }
+  }
+
+  /// Dirty rectangles from the most recent `capture_with_metadata` or `capture_incremental`
+  /// call (empty when `capture_incremental` fell back to a full copy).
+  pub fn dirty_rects(&self) -> &[RECT] {
+    let offset = self.move_rect_count * std::mem::size_of::<DXGI_OUTDUPL_MOVE_RECT>();
+    let ptr = self.metadata_buffer[offset..].as_ptr() as *const RECT;
+    unsafe { std::slice::from_raw_parts(ptr, self.dirty_rect_count) }
+  }
+
+  /// Like [`Capturer::capture`] but also populates [`Self::move_rects`] and
+  /// [`Self::dirty_rects`] from the frame's change metadata, when DXGI reports any.
+  pub fn capture_with_metadata(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let frame_info = self.capture()?;
+    (self.move_rect_count, self.dirty_rect_count) = self
+      .ctx
+      .frame_metadata(&frame_info, &mut self.metadata_buffer)?;
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_with_metadata(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_with_metadata()
+  }
+
+  /// Like [`Capturer::capture`], but rewrites the captured frame so it is always upright,
+  /// regardless of the output's rotation. An unrotated frame is copied with one memcpy like
+  /// `capture`; a rotated frame is transformed pixel by pixel.
+  pub fn capture_upright(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self
+      .ctx
+      .capture_frame_upright(self.buffer.as_mut_ptr(), &self.texture)
+  }
+
+  pub fn safe_capture_upright(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_upright()
+  }
+
+  /// Like [`Capturer::capture`], but draws the hardware cursor onto the captured frame using
+  /// [`crate::utils::composite_cursor`]. The pointer shape is only re-fetched when
+  /// `frame_info.mouse_updated()` reports a change; otherwise the last cached shape is reused.
+  pub fn capture_with_cursor(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let (frame_info, pointer_shape_info) = self.capture_with_pointer_shape()?;
+    if pointer_shape_info.is_some() {
+      self.cached_pointer_shape_info = pointer_shape_info;
+    }
+
+    if frame_info.PointerPosition.Visible.as_bool() {
+      if let Some(shape) = &self.cached_pointer_shape_info {
+        let desc = self.dxgi_output_desc()?;
+        composite_cursor(
+          &mut self.buffer,
+          desc.width(),
+          desc.height(),
+          frame_info.PointerPosition.Position,
+          shape,
+          &self.pointer_shape_buffer[..self.pointer_shape_buffer_size],
+        );
+      }
+    }
+
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_with_cursor(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_with_cursor()
+  }
+
+  /// Like [`Capturer::capture`], but only transfers the regions DXGI reports as changed once
+  /// [`Self::buffer`] already holds a previous frame, patching them in place instead of
+  /// re-copying the whole desktop. Also refreshes [`Self::move_rects`]/[`Self::dirty_rects`].
+  /// Falls back to a full copy on the first call, and again on the call right after the
+  /// duplication session had to be rebuilt, since `buffer` can no longer be trusted to match.
+  pub fn capture_incremental(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let desc = self.dxgi_output_desc()?;
+    let (frame_info, move_rect_count, dirty_rect_count, reacquired) =
+      self.ctx.capture_frame_incremental(
+        self.buffer.as_mut_ptr(),
+        self.buffer.len(),
+        desc.width(),
+        &self.texture,
+        &mut self.metadata_buffer,
+        self.has_previous_frame,
+      )?;
+    self.move_rect_count = move_rect_count;
+    self.dirty_rect_count = dirty_rect_count;
+    self.has_previous_frame = !reacquired;
+    Ok(frame_info)
+  }
+
+  pub fn safe_capture_incremental(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    self.check_buffer()?;
+    self.capture_incremental()
+  }
+
+  /// Whether the pointer shape changed on the most recent [`Self::capture_with_pointer_shape`]
+  /// call, i.e. whether [`Self::pointer_shape_buffer`] now differs from before that call.
+  pub fn pointer_shape_updated(&self) -> bool {
+    self.pointer_shape_buffer_size != self.last_pointer_shape_buffer_size || {
+      let len = self.pointer_shape_buffer_size;
+      self.pointer_shape_buffer[..len] != self.last_pointer_shape_buffer[..len]
+    }
+  }
 }
 
 impl Capturer for SimpleCapturer<'_> {
   fn dxgi_output_desc(&self) -> Result<DXGI_OUTPUT_DESC> {
-    self.ctx.dxgi_output_desc()
+    self.ctx.get_desc()
   }
 
   fn buffer(&self) -> &[u8] {
@@ -61,8 +177,7 @@ impl Capturer for SimpleCapturer<'_> {
 
   fn check_buffer(&self) -> Result<()> {
     let desc = self.dxgi_output_desc()?;
-    let dpi = self.ctx.effective_dpi(&desc)?;
-    if self.buffer.len() < desc.calc_buffer_size(dpi) {
+    if self.buffer.len() < desc.calc_buffer_size() {
       Err("Invalid buffer length".into())
     } else {
       Ok(())
@@ -73,17 +188,10 @@ impl Capturer for SimpleCapturer<'_> {
     &self.pointer_shape_buffer[..self.pointer_shape_buffer_size]
   }
 
-  fn pointer_shape_updated(&self) -> bool {
-    self.pointer_shape_buffer_size != self.last_pointer_shape_buffer_size || {
-      let len = self.pointer_shape_buffer_size;
-      self.pointer_shape_buffer[..len] != self.last_pointer_shape_buffer[..len]
-    }
-  }
-
   fn capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
     self
       .ctx
-      .capture(self.buffer.as_mut_ptr(), self.buffer.len(), &self.texture)
+      .capture_frame(self.buffer.as_mut_ptr(), self.buffer.len(), &self.texture)
   }
 
   fn safe_capture(&mut self) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
@@ -135,7 +243,7 @@ impl Capturer for SimpleCapturer<'_> {
   }
 }
 
-impl DuplicationContext {
+impl DuplicateContext {
   pub fn simple_capturer(&self) -> Result<SimpleCapturer> {
     SimpleCapturer::new(self)
   }
@@ -149,7 +257,7 @@ mod tests {
 
   #[test]
   fn simple_capturer() {
-    let manager = Manager::default().unwrap();
+    let manager = Manager::new().unwrap();
     assert_ne!(manager.contexts.len(), 0);
 
     let mut capturer = manager.contexts[0].simple_capturer().unwrap();
@@ -171,6 +279,10 @@ mod tests {
     }
     assert!(!all_zero);
 
+    // capture_upright should succeed regardless of the output's rotation, and write into the
+    // same buffer size as a regular capture (width*height*4 either way).
+    capturer.safe_capture_upright().unwrap();
+
     // sleep for a while before capture to wait system to update the mouse
     thread::sleep(Duration::from_millis(1000));
 
@@ -188,5 +300,15 @@ mod tests {
       }
     }
     assert!(!all_zero);
+
+    // capture_with_cursor should composite the cached pointer shape onto the buffer without
+    // erroring, whether or not this particular frame reports a new shape.
+    capturer.safe_capture_with_cursor().unwrap();
+
+    // first call has no previous frame to diff against, so it falls back to a full copy
+    capturer.safe_capture_incremental().unwrap();
+    thread::sleep(Duration::from_millis(100));
+    // second call can actually patch in move/dirty rects
+    capturer.safe_capture_incremental().unwrap();
   }
 }
@@ -1,7 +1,8 @@
-use std::ptr;
+use std::{cell::RefCell, mem, ptr};
 
 use windows::{
   core::ComInterface,
+  Win32::Foundation::RECT,
   Win32::Graphics::{
     Direct3D11::{
       ID3D11Device, ID3D11DeviceContext, ID3D11Texture2D, D3D11_BIND_FLAG, D3D11_CPU_ACCESS_READ,
@@ -9,20 +10,28 @@ use windows::{
     },
     Dxgi::{
       Common::{DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_SAMPLE_DESC},
-      IDXGIOutput1, IDXGIOutputDuplication, IDXGIResource, IDXGISurface1, DXGI_MAPPED_RECT,
-      DXGI_MAP_READ, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC, DXGI_RESOURCE_PRIORITY_MAXIMUM,
+      IDXGIOutput1, IDXGIOutputDuplication, IDXGIResource, IDXGISurface1, DXGI_ERROR_ACCESS_DENIED,
+      DXGI_ERROR_ACCESS_LOST, DXGI_ERROR_WAIT_TIMEOUT, DXGI_MAPPED_RECT, DXGI_MAP_READ,
+      DXGI_MODE_ROTATION, DXGI_MODE_ROTATION_IDENTITY, DXGI_MODE_ROTATION_ROTATE180,
+      DXGI_MODE_ROTATION_ROTATE270, DXGI_MODE_ROTATION_ROTATE90, DXGI_MODE_ROTATION_UNSPECIFIED,
+      DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_MOVE_RECT, DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+      DXGI_OUTPUT_DESC, DXGI_RESOURCE_PRIORITY_MAXIMUM,
     },
   },
 };
 
-use crate::utils::Dimension;
+use crate::model::{Error, Result};
+use crate::utils::OutputDescExt;
 
 pub struct DuplicateContext {
   device: ID3D11Device,
   device_context: ID3D11DeviceContext,
   timeout_ms: u32,
   output: IDXGIOutput1,
-  output_duplication: IDXGIOutputDuplication,
+  // Wrapped in a `RefCell` because `AcquireNextFrame` needs to rebuild this on
+  // `DXGI_ERROR_ACCESS_LOST`/`DXGI_ERROR_ACCESS_DENIED` while every other method here only
+  // takes `&self`.
+  output_duplication: RefCell<IDXGIOutputDuplication>,
 }
 
 impl DuplicateContext {
@@ -38,21 +47,32 @@ impl DuplicateContext {
       device_context,
       timeout_ms,
       output,
-      output_duplication,
+      output_duplication: RefCell::new(output_duplication),
     }
   }
 
-  pub fn get_desc(&self) -> DXGI_OUTPUT_DESC {
+  /// The D3D11 device backing this duplication session, for callers that want to build their
+  /// own GPU interop (e.g. a shared keyed-mutex texture) instead of reading back to the CPU.
+  pub fn device(&self) -> &ID3D11Device {
+    &self.device
+  }
+
+  /// The immediate context tied to [`Self::device`].
+  pub fn device_context(&self) -> &ID3D11DeviceContext {
+    &self.device_context
+  }
+
+  pub fn get_desc(&self) -> Result<DXGI_OUTPUT_DESC> {
     unsafe {
       let mut desc = DXGI_OUTPUT_DESC::default();
-      self.output.GetDesc(&mut desc).unwrap();
-      desc
+      self.output.GetDesc(&mut desc)?;
+      Ok(desc)
     }
   }
 
-  pub fn create_readable_texture(&self) -> ID3D11Texture2D {
+  pub fn create_readable_texture(&self) -> Result<ID3D11Texture2D> {
     unsafe {
-      let desc = self.get_desc();
+      let desc = self.get_desc()?;
 
       // create a readable texture description
       let texture_desc = D3D11_TEXTURE2D_DESC {
@@ -75,48 +95,553 @@ impl DuplicateContext {
       let mut readable_texture: Option<ID3D11Texture2D> = None.clone();
       self
         .device
-        .CreateTexture2D(&texture_desc, None, Some(&mut readable_texture))
-        .unwrap();
+        .CreateTexture2D(&texture_desc, None, Some(&mut readable_texture))?;
       let readable_texture = readable_texture.unwrap();
       // Lower priorities causes stuff to be needlessly copied from gpu to ram,
       // causing huge ram usage on some systems.
       // https://github.com/bryal/dxgcap-rs/blob/208d93368bc64aed783791242410459c878a10fb/src/lib.rs#L225
       readable_texture.SetEvictionPriority(DXGI_RESOURCE_PRIORITY_MAXIMUM.0);
 
-      readable_texture
+      Ok(readable_texture)
     }
   }
 
-  pub fn acquire_next_frame(&self, readable_texture: &ID3D11Texture2D) -> IDXGISurface1 {
+  /// Re-run `IDXGIOutput1::DuplicateOutput` to rebuild the duplication session after it was
+  /// dropped by the OS (desktop mode switch, secure-desktop transition, fullscreen exclusive
+  /// app, ...).
+  fn reacquire_output_duplication(&self) -> Result<()> {
+    unsafe {
+      let output_duplication = self.output.DuplicateOutput(&self.device)?;
+      *self.output_duplication.borrow_mut() = output_duplication;
+      Ok(())
+    }
+  }
+
+  pub fn acquire_next_frame(
+    &self,
+    readable_texture: &ID3D11Texture2D,
+  ) -> Result<(IDXGISurface1, DXGI_OUTDUPL_FRAME_INFO)> {
     unsafe {
-      // acquire GPU texture
       let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
       let mut resource: Option<IDXGIResource> = None.clone();
-      self
+
+      match self
         .output_duplication
+        .borrow()
         .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
-        .unwrap();
-      let texture: ID3D11Texture2D = resource.unwrap().cast().unwrap();
+      {
+        Ok(()) => {}
+        Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => return Err(Error::Timeout),
+        Err(e) if e.code() == DXGI_ERROR_ACCESS_LOST || e.code() == DXGI_ERROR_ACCESS_DENIED => {
+          // transparently rebuild the duplication session and retry once
+          self.reacquire_output_duplication()?;
+          frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+          resource = None;
+          self
+            .output_duplication
+            .borrow()
+            .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
+            .map_err(|_| Error::AccessLost)?;
+        }
+        Err(e) => return Err(e.into()),
+      }
+
+      // acquire GPU texture
+      let texture: ID3D11Texture2D = resource.unwrap().cast()?;
 
       // copy GPU texture to readable texture
       self.device_context.CopyResource(readable_texture, &texture);
 
       // release GPU texture
-      self.output_duplication.ReleaseFrame().unwrap();
+      self.output_duplication.borrow().ReleaseFrame()?;
 
-      readable_texture.cast().unwrap()
+      Ok((readable_texture.cast()?, frame_info))
     }
   }
 
-  pub fn capture_frame(&self, dest: *mut u8, len: usize, readable_texture: &ID3D11Texture2D) {
+  /// Copy the next duplicated frame straight into `dest_texture` without reading it back to
+  /// the CPU. `dest_texture` does not have to be a CPU-readable staging texture: it can be a
+  /// `D3D11_USAGE_DEFAULT` texture (e.g. one backed by a shared keyed mutex) for zero-copy GPU
+  /// interop with an encoder or another device.
+  pub fn capture_into(&self, dest_texture: &ID3D11Texture2D) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    let (_, frame_info) = self.acquire_next_frame(dest_texture)?;
+    Ok(frame_info)
+  }
+
+  pub fn capture_frame(
+    &self,
+    dest: *mut u8,
+    len: usize,
+    readable_texture: &ID3D11Texture2D,
+  ) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
     unsafe {
-      let frame = self.acquire_next_frame(readable_texture);
+      let (frame, frame_info) = self.acquire_next_frame(readable_texture)?;
       let mut mapped_surface = DXGI_MAPPED_RECT::default();
-      frame.Map(&mut mapped_surface, DXGI_MAP_READ).unwrap();
+      frame.Map(&mut mapped_surface, DXGI_MAP_READ)?;
 
       ptr::copy_nonoverlapping(mapped_surface.pBits, dest, len);
 
-      frame.Unmap().unwrap();
+      frame.Unmap()?;
+
+      Ok(frame_info)
+    }
+  }
+
+  /// Like [`Self::capture_frame`], but also fetches the hardware cursor's shape when DXGI
+  /// reports a new one (`frame_info.PointerShapeBufferSize > 0`), writing it into `pointer_shape_buffer`
+  /// (grown to fit, and reused across calls the same way `metadata_buffer` is). Returns `None`
+  /// for the shape when this frame carries no new one, in which case callers should keep using
+  /// whatever shape the previous call returned.
+  pub fn capture_with_pointer_shape(
+    &self,
+    dest: *mut u8,
+    dest_len: usize,
+    readable_texture: &ID3D11Texture2D,
+    pointer_shape_buffer: &mut Vec<u8>,
+  ) -> Result<(
+    DXGI_OUTDUPL_FRAME_INFO,
+    Option<DXGI_OUTDUPL_POINTER_SHAPE_INFO>,
+  )> {
+    unsafe {
+      let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+      let mut resource: Option<IDXGIResource> = None.clone();
+
+      match self
+        .output_duplication
+        .borrow()
+        .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
+      {
+        Ok(()) => {}
+        Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => return Err(Error::Timeout),
+        Err(e) if e.code() == DXGI_ERROR_ACCESS_LOST || e.code() == DXGI_ERROR_ACCESS_DENIED => {
+          self.reacquire_output_duplication()?;
+          frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+          resource = None;
+          self
+            .output_duplication
+            .borrow()
+            .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
+            .map_err(|_| Error::AccessLost)?;
+        }
+        Err(e) => return Err(e.into()),
+      }
+
+      let texture: ID3D11Texture2D = resource.unwrap().cast()?;
+      self.device_context.CopyResource(readable_texture, &texture);
+
+      // The pointer shape must be read while the frame is still acquired, before `ReleaseFrame`.
+      let pointer_shape_info = if frame_info.PointerShapeBufferSize > 0 {
+        let buffer_size = frame_info.PointerShapeBufferSize as usize;
+        if pointer_shape_buffer.len() < buffer_size {
+          pointer_shape_buffer.resize(buffer_size, 0);
+        }
+        let mut shape_info = DXGI_OUTDUPL_POINTER_SHAPE_INFO::default();
+        let mut bytes_written = 0u32;
+        self.output_duplication.borrow().GetFramePointerShape(
+          buffer_size as u32,
+          pointer_shape_buffer.as_mut_ptr() as *mut _,
+          &mut bytes_written,
+          &mut shape_info,
+        )?;
+        Some(shape_info)
+      } else {
+        None
+      };
+
+      self.output_duplication.borrow().ReleaseFrame()?;
+
+      let frame: IDXGISurface1 = readable_texture.cast()?;
+      let mut mapped_surface = DXGI_MAPPED_RECT::default();
+      frame.Map(&mut mapped_surface, DXGI_MAP_READ)?;
+      ptr::copy_nonoverlapping(mapped_surface.pBits, dest, dest_len);
+      frame.Unmap()?;
+
+      Ok((frame_info, pointer_shape_info))
+    }
+  }
+
+  /// Like [`Self::capture_frame`], but always writes an upright image to `dest`, undoing
+  /// whatever rotation the output reports (`DXGI_OUTPUT_DESC.Rotation`). The duplicated
+  /// texture keeps the panel's native orientation, so `dest` must be sized for the *upright*
+  /// dimensions (see [`crate::utils::OutputDescExt::calc_buffer_size`]).
+  ///
+  /// An unrotated output is copied with a single memcpy, like `capture_frame`. For a rotated
+  /// output every pixel is moved individually, respecting the source `Pitch`.
+  pub fn capture_frame_upright(
+    &self,
+    dest: *mut u8,
+    readable_texture: &ID3D11Texture2D,
+  ) -> Result<DXGI_OUTDUPL_FRAME_INFO> {
+    unsafe {
+      let desc = self.get_desc()?;
+      let (frame, frame_info) = self.acquire_next_frame(readable_texture)?;
+      let mut mapped_surface = DXGI_MAPPED_RECT::default();
+      frame.Map(&mut mapped_surface, DXGI_MAP_READ)?;
+
+      match desc.Rotation {
+        DXGI_MODE_ROTATION_IDENTITY | DXGI_MODE_ROTATION_UNSPECIFIED => {
+          let len = desc.calc_buffer_size();
+          ptr::copy_nonoverlapping(mapped_surface.pBits, dest, len);
+        }
+        rotation => Self::rotate_bgra_into(
+          mapped_surface.pBits,
+          mapped_surface.Pitch as usize,
+          dest,
+          desc.width(),
+          desc.height(),
+          rotation,
+        ),
+      }
+
+      frame.Unmap()?;
+
+      Ok(frame_info)
+    }
+  }
+
+  /// Rewrite a mapped BGRA32 surface of native size `src_width`x`src_height` into `dest` so
+  /// the result is upright, undoing `rotation`. Walks the source row-by-row so the source
+  /// `Pitch` (not necessarily `src_width * 4`) is respected; for 90/270 this transposes, so
+  /// it cannot be expressed as a per-row memcpy.
+  unsafe fn rotate_bgra_into(
+    src: *const u8,
+    src_pitch: usize,
+    dest: *mut u8,
+    src_width: u32,
+    src_height: u32,
+    rotation: DXGI_MODE_ROTATION,
+  ) {
+    const BYTES_PER_PIXEL: usize = 4;
+    let src_width = src_width as usize;
+    let src_height = src_height as usize;
+    let dest_width = match rotation {
+      DXGI_MODE_ROTATION_ROTATE90 | DXGI_MODE_ROTATION_ROTATE270 => src_height,
+      _ => src_width,
+    };
+
+    for src_y in 0..src_height {
+      let src_row = src.add(src_y * src_pitch);
+      for src_x in 0..src_width {
+        let (dest_x, dest_y) = match rotation {
+          DXGI_MODE_ROTATION_ROTATE90 => (src_height - 1 - src_y, src_x),
+          DXGI_MODE_ROTATION_ROTATE180 => (src_width - 1 - src_x, src_height - 1 - src_y),
+          DXGI_MODE_ROTATION_ROTATE270 => (src_y, src_width - 1 - src_x),
+          _ => (src_x, src_y),
+        };
+        let dest_offset = (dest_y * dest_width + dest_x) * BYTES_PER_PIXEL;
+        ptr::copy_nonoverlapping(
+          src_row.add(src_x * BYTES_PER_PIXEL),
+          dest.add(dest_offset),
+          BYTES_PER_PIXEL,
+        );
+      }
+    }
+  }
+
+  /// Like [`Self::capture_frame`], but when `has_previous_frame` is true and DXGI reports
+  /// change metadata for this frame, only the changed regions are copied: `dest` is assumed to
+  /// already hold the previous frame (tightly packed, `dest_width * 4` bytes per row) and is
+  /// patched in place using the move/dirty rectangles, instead of re-copying the whole desktop.
+  /// Falls back to a full copy on the first frame (`has_previous_frame == false`), whenever
+  /// DXGI reports no metadata, or when the duplication session had to be rebuilt mid-call (see
+  /// [`Self::reacquire_output_duplication`]) since `dest` may no longer match the new output's
+  /// resolution or contents. Returns the frame info, the move/dirty rect counts applied
+  /// (mirroring [`Self::frame_metadata`], both zero when a full copy was done instead), and
+  /// whether a reacquire happened: callers must pass `false` as `has_previous_frame` on their
+  /// next call when this is `true`.
+  pub fn capture_frame_incremental(
+    &self,
+    dest: *mut u8,
+    dest_len: usize,
+    dest_width: u32,
+    readable_texture: &ID3D11Texture2D,
+    metadata_buffer: &mut Vec<u8>,
+    has_previous_frame: bool,
+  ) -> Result<(DXGI_OUTDUPL_FRAME_INFO, usize, usize, bool)> {
+    unsafe {
+      let mut frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+      let mut resource: Option<IDXGIResource> = None.clone();
+      let mut reacquired = false;
+
+      match self
+        .output_duplication
+        .borrow()
+        .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
+      {
+        Ok(()) => {}
+        Err(e) if e.code() == DXGI_ERROR_WAIT_TIMEOUT => return Err(Error::Timeout),
+        Err(e) if e.code() == DXGI_ERROR_ACCESS_LOST || e.code() == DXGI_ERROR_ACCESS_DENIED => {
+          self.reacquire_output_duplication()?;
+          reacquired = true;
+          frame_info = DXGI_OUTDUPL_FRAME_INFO::default();
+          resource = None;
+          self
+            .output_duplication
+            .borrow()
+            .AcquireNextFrame(self.timeout_ms, &mut frame_info, &mut resource)
+            .map_err(|_| Error::AccessLost)?;
+        }
+        Err(e) => return Err(e.into()),
+      }
+
+      let texture: ID3D11Texture2D = resource.unwrap().cast()?;
+      self.device_context.CopyResource(readable_texture, &texture);
+
+      // Move/dirty rects must be read while the frame is still acquired, before `ReleaseFrame`.
+      let incremental = has_previous_frame && !reacquired && frame_info.TotalMetadataBufferSize > 0;
+      let (move_rect_count, dirty_rect_count) = if incremental {
+        self.frame_metadata(&frame_info, metadata_buffer)?
+      } else {
+        (0, 0)
+      };
+
+      self.output_duplication.borrow().ReleaseFrame()?;
+
+      let frame: IDXGISurface1 = readable_texture.cast()?;
+      let mut mapped_surface = DXGI_MAPPED_RECT::default();
+      frame.Map(&mut mapped_surface, DXGI_MAP_READ)?;
+
+      if incremental {
+        let move_rects = std::slice::from_raw_parts(
+          metadata_buffer.as_ptr() as *const DXGI_OUTDUPL_MOVE_RECT,
+          move_rect_count,
+        );
+        let dirty_rect_offset = move_rect_count * mem::size_of::<DXGI_OUTDUPL_MOVE_RECT>();
+        let dirty_rects = std::slice::from_raw_parts(
+          metadata_buffer[dirty_rect_offset..].as_ptr() as *const RECT,
+          dirty_rect_count,
+        );
+
+        // Apply moves first, exactly as the desktop compositor did, then patch in the dirty
+        // regions on top.
+        for mv in move_rects {
+          Self::apply_move_rect(dest, dest_width, mv);
+        }
+        for rect in dirty_rects {
+          Self::copy_dirty_rect(
+            mapped_surface.pBits,
+            mapped_surface.Pitch as usize,
+            dest,
+            dest_width,
+            rect,
+          );
+        }
+      } else {
+        ptr::copy_nonoverlapping(mapped_surface.pBits, dest, dest_len);
+      }
+
+      frame.Unmap()?;
+
+      Ok((frame_info, move_rect_count, dirty_rect_count, reacquired))
     }
   }
+
+  /// In-buffer block copy of `dest`'s `mv.SourcePoint` rectangle to `mv.DestinationRect`, both
+  /// within the same `dest_width`-wide BGRA32 buffer. Walks rows in the direction that reads
+  /// each one before it is overwritten, since source and destination can overlap when the move
+  /// distance is small.
+  unsafe fn apply_move_rect(dest: *mut u8, dest_width: u32, mv: &DXGI_OUTDUPL_MOVE_RECT) {
+    const BYTES_PER_PIXEL: usize = 4;
+    let dest_stride = dest_width as usize * BYTES_PER_PIXEL;
+    let width = (mv.DestinationRect.right - mv.DestinationRect.left) as usize * BYTES_PER_PIXEL;
+    let height = (mv.DestinationRect.bottom - mv.DestinationRect.top) as usize;
+    let src_x = mv.SourcePoint.x as usize * BYTES_PER_PIXEL;
+    let src_y = mv.SourcePoint.y as usize;
+    let dest_x = mv.DestinationRect.left as usize * BYTES_PER_PIXEL;
+    let dest_y = mv.DestinationRect.top as usize;
+
+    let copy_row = |row: usize| {
+      let src_row = dest.add((src_y + row) * dest_stride + src_x);
+      let dest_row = dest.add((dest_y + row) * dest_stride + dest_x);
+      ptr::copy(src_row, dest_row, width);
+    };
+
+    if dest_y > src_y {
+      (0..height).rev().for_each(copy_row);
+    } else {
+      (0..height).for_each(copy_row);
+    }
+  }
+
+  /// Copy a single dirty `rect` out of a mapped BGRA32 surface (`src`, `src_pitch` bytes per
+  /// row) into the matching offset of the `dest_width`-wide `dest` buffer.
+  unsafe fn copy_dirty_rect(src: *const u8, src_pitch: usize, dest: *mut u8, dest_width: u32, rect: &RECT) {
+    const BYTES_PER_PIXEL: usize = 4;
+    let dest_stride = dest_width as usize * BYTES_PER_PIXEL;
+    let x = rect.left as usize * BYTES_PER_PIXEL;
+    let y = rect.top as usize;
+    let width = (rect.right - rect.left) as usize * BYTES_PER_PIXEL;
+    let height = (rect.bottom - rect.top) as usize;
+
+    for row in 0..height {
+      let src_row = src.add((y + row) * src_pitch + x);
+      let dest_row = dest.add((y + row) * dest_stride + x);
+      ptr::copy_nonoverlapping(src_row, dest_row, width);
+    }
+  }
+
+  /// Read the move/dirty rectangles DXGI reports for the frame described by `frame_info`.
+  ///
+  /// `metadata_buffer` is grown to fit both arrays back-to-back (move rects first, then dirty
+  /// rects) and reused across calls, mirroring how `pointer_shape_buffer` is reused by callers.
+  /// Returns the number of move rects and dirty rects written into the buffer.
+  pub fn frame_metadata(
+    &self,
+    frame_info: &DXGI_OUTDUPL_FRAME_INFO,
+    metadata_buffer: &mut Vec<u8>,
+  ) -> Result<(usize, usize)> {
+    if frame_info.TotalMetadataBufferSize == 0 {
+      return Ok((0, 0));
+    }
+
+    unsafe {
+      let buffer_size = frame_info.TotalMetadataBufferSize as usize;
+      if metadata_buffer.len() < buffer_size {
+        metadata_buffer.resize(buffer_size, 0);
+      }
+
+      let mut move_rect_bytes = 0u32;
+      self.output_duplication.borrow().GetFrameMoveRects(
+        buffer_size as u32,
+        metadata_buffer.as_mut_ptr() as *mut DXGI_OUTDUPL_MOVE_RECT,
+        &mut move_rect_bytes,
+      )?;
+      let move_rect_count = move_rect_bytes as usize / mem::size_of::<DXGI_OUTDUPL_MOVE_RECT>();
+
+      let dirty_rect_buffer = &mut metadata_buffer[move_rect_bytes as usize..buffer_size];
+      let mut dirty_rect_bytes = 0u32;
+      self.output_duplication.borrow().GetFrameDirtyRects(
+        dirty_rect_buffer.len() as u32,
+        dirty_rect_buffer.as_mut_ptr() as *mut RECT,
+        &mut dirty_rect_bytes,
+      )?;
+      let dirty_rect_count = dirty_rect_bytes as usize / mem::size_of::<RECT>();
+
+      Ok((move_rect_count, dirty_rect_count))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use windows::Win32::Foundation::{POINT, RECT};
+  use windows::Win32::Graphics::Dxgi::{
+    DXGI_MODE_ROTATION_ROTATE270, DXGI_MODE_ROTATION_ROTATE90, DXGI_OUTDUPL_MOVE_RECT,
+  };
+
+  use super::DuplicateContext;
+
+  /// Fill a BYTES_PER_PIXEL=4 buffer with each pixel set to `id(x, y)`, repeated across all 4
+  /// channels, so a pixel's position can be recovered by reading any one of its bytes.
+  fn filled(width: usize, height: usize, pitch: usize, id: impl Fn(usize, usize) -> u8) -> Vec<u8> {
+    let mut buf = vec![0u8; height * pitch];
+    for y in 0..height {
+      for x in 0..width {
+        let value = id(x, y);
+        buf[y * pitch + x * 4..y * pitch + x * 4 + 4].fill(value);
+      }
+    }
+    buf
+  }
+
+  #[test]
+  fn rotate_bgra_into_rotate90_transposes_clockwise() {
+    // 2x2 source, pitch padded past the 8 bytes the pixels need to catch bugs that assume
+    // pitch == width * 4.
+    let src = filled(2, 2, 12, |x, y| (y * 2 + x + 1) as u8); // 1,2 / 3,4
+    let mut dest = vec![0u8; 2 * 2 * 4];
+
+    unsafe {
+      DuplicateContext::rotate_bgra_into(src.as_ptr(), 12, dest.as_mut_ptr(), 2, 2, DXGI_MODE_ROTATION_ROTATE90);
+    }
+
+    // clockwise rotation: source column 0 (top-to-bottom: 1, 3) becomes the upright top row.
+    let pixel = |buf: &[u8], x: usize, y: usize| buf[(y * 2 + x) * 4];
+    assert_eq!(pixel(&dest, 0, 0), 3);
+    assert_eq!(pixel(&dest, 1, 0), 1);
+    assert_eq!(pixel(&dest, 0, 1), 4);
+    assert_eq!(pixel(&dest, 1, 1), 2);
+  }
+
+  #[test]
+  fn rotate_bgra_into_rotate270_is_the_opposite_direction_of_rotate90() {
+    let src = filled(2, 2, 8, |x, y| (y * 2 + x + 1) as u8); // 1,2 / 3,4
+    let mut dest = vec![0u8; 2 * 2 * 4];
+
+    unsafe {
+      DuplicateContext::rotate_bgra_into(src.as_ptr(), 8, dest.as_mut_ptr(), 2, 2, DXGI_MODE_ROTATION_ROTATE270);
+    }
+
+    let pixel = |buf: &[u8], x: usize, y: usize| buf[(y * 2 + x) * 4];
+    assert_eq!(pixel(&dest, 0, 0), 2);
+    assert_eq!(pixel(&dest, 1, 0), 4);
+    assert_eq!(pixel(&dest, 0, 1), 1);
+    assert_eq!(pixel(&dest, 1, 1), 3);
+  }
+
+  #[test]
+  fn apply_move_rect_copies_non_overlapping_block() {
+    let dest_width = 4;
+    let mut dest = filled(4, 4, 16, |x, y| (y * 4 + x) as u8);
+
+    let mv = DXGI_OUTDUPL_MOVE_RECT {
+      SourcePoint: POINT { x: 0, y: 0 },
+      DestinationRect: RECT { left: 2, top: 2, right: 4, bottom: 4 },
+    };
+    unsafe { DuplicateContext::apply_move_rect(dest.as_mut_ptr(), dest_width, &mv) };
+
+    let pixel = |buf: &[u8], x: usize, y: usize| buf[(y * 4 + x) * 4];
+    // destination block now holds the source block's original values
+    assert_eq!(pixel(&dest, 2, 2), 0);
+    assert_eq!(pixel(&dest, 3, 2), 1);
+    assert_eq!(pixel(&dest, 2, 3), 4);
+    assert_eq!(pixel(&dest, 3, 3), 5);
+    // source block is untouched since the two regions don't overlap
+    assert_eq!(pixel(&dest, 0, 0), 0);
+    assert_eq!(pixel(&dest, 1, 1), 5);
+  }
+
+  #[test]
+  fn apply_move_rect_walks_overlapping_shift_in_safe_order() {
+    // 1-pixel-wide buffer, shifting 3 rows down by 1 so source and destination overlap by 2
+    // rows. The destination is below the source, so rows must be copied bottom-up or a row
+    // would be overwritten before it's read as someone else's source.
+    let dest_width = 1;
+    let mut dest: Vec<u8> = [10u8, 20, 30, 40].iter().flat_map(|&v| [v; 4]).collect();
+
+    let mv = DXGI_OUTDUPL_MOVE_RECT {
+      SourcePoint: POINT { x: 0, y: 0 },
+      DestinationRect: RECT { left: 0, top: 1, right: 1, bottom: 4 },
+    };
+    unsafe { DuplicateContext::apply_move_rect(dest.as_mut_ptr(), dest_width, &mv) };
+
+    let row = |buf: &[u8], y: usize| buf[y * 4];
+    assert_eq!(row(&dest, 0), 10); // outside the destination rect, untouched
+    assert_eq!(row(&dest, 1), 10);
+    assert_eq!(row(&dest, 2), 20);
+    assert_eq!(row(&dest, 3), 30);
+  }
+
+  #[test]
+  fn copy_dirty_rect_respects_pitch_and_offset() {
+    let src_width = 4;
+    let src_pitch = 20; // padded past the 16 bytes 4 BGRA32 pixels need
+    let src = filled(src_width, 2, src_pitch, |x, y| (y * 4 + x) as u8);
+
+    let dest_width = 4;
+    let mut dest = vec![0xFFu8; dest_width * 2 * 4];
+
+    let rect = RECT { left: 1, top: 0, right: 3, bottom: 2 };
+    unsafe { DuplicateContext::copy_dirty_rect(src.as_ptr(), src_pitch, dest.as_mut_ptr(), dest_width as u32, &rect) };
+
+    let pixel = |buf: &[u8], x: usize, y: usize| buf[(y * dest_width + x) * 4];
+    // columns 1..3 were patched from the source
+    assert_eq!(pixel(&dest, 1, 0), 1);
+    assert_eq!(pixel(&dest, 2, 0), 2);
+    assert_eq!(pixel(&dest, 1, 1), 5);
+    assert_eq!(pixel(&dest, 2, 1), 6);
+    // columns outside the rect are untouched
+    assert_eq!(pixel(&dest, 0, 0), 0xFF);
+    assert_eq!(pixel(&dest, 3, 1), 0xFF);
+  }
 }
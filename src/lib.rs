@@ -0,0 +1,9 @@
+pub mod capturer;
+pub mod duplicate_context;
+pub mod manager;
+pub mod model;
+pub mod utils;
+
+pub use duplicate_context::DuplicateContext;
+pub use manager::Manager;
+pub use model::{Error, Result};
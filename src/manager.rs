@@ -0,0 +1,101 @@
+use windows::{
+  core::ComInterface,
+  Win32::Graphics::{
+    Direct3D::D3D_DRIVER_TYPE_UNKNOWN,
+    Direct3D11::{D3D11CreateDevice, D3D11_CREATE_DEVICE_FLAG, D3D11_SDK_VERSION},
+    Dxgi::{CreateDXGIFactory1, IDXGIAdapter1, IDXGIFactory1, IDXGIOutput1},
+  },
+};
+
+use crate::duplicate_context::DuplicateContext;
+use crate::model::Result;
+
+/// How long [`DuplicateContext::acquire_next_frame`] (and friends) wait for a new frame before
+/// returning [`crate::model::Error::Timeout`].
+const DEFAULT_TIMEOUT_MS: u32 = 300;
+
+/// Enumerates every display output on every graphics adapter and holds a [`DuplicateContext`]
+/// for each, since `IDXGIOutputDuplication` has no other entry point than walking
+/// `IDXGIFactory1::EnumAdapters1` / `IDXGIAdapter1::EnumOutputs`.
+pub struct Manager {
+  pub contexts: Vec<DuplicateContext>,
+}
+
+impl Manager {
+  /// Enumerate all outputs now and build a context for each.
+  pub fn new() -> Result<Self> {
+    let mut manager = Self {
+      contexts: Vec::new(),
+    };
+    manager.refresh()?;
+    Ok(manager)
+  }
+
+  /// Re-enumerate all outputs, replacing [`Self::contexts`]. Call this after a display is
+  /// connected/disconnected; existing [`DuplicateContext`]s only recover from mode switches
+  /// within the same output (see `DuplicateContext::reacquire_output_duplication`), not from
+  /// outputs appearing or disappearing.
+  pub fn refresh(&mut self) -> Result<()> {
+    self.contexts = Self::enumerate()?;
+    Ok(())
+  }
+
+  fn enumerate() -> Result<Vec<DuplicateContext>> {
+    unsafe {
+      let factory: IDXGIFactory1 = CreateDXGIFactory1()?;
+      let mut contexts = Vec::new();
+
+      let mut adapter_index = 0;
+      while let Ok(adapter) = factory.EnumAdapters1(adapter_index) {
+        contexts.extend(Self::contexts_for_adapter(&adapter)?);
+        adapter_index += 1;
+      }
+
+      Ok(contexts)
+    }
+  }
+
+  unsafe fn contexts_for_adapter(adapter: &IDXGIAdapter1) -> Result<Vec<DuplicateContext>> {
+    let mut device = None;
+    let mut device_context = None;
+    D3D11CreateDevice(
+      adapter,
+      D3D_DRIVER_TYPE_UNKNOWN,
+      None,
+      D3D11_CREATE_DEVICE_FLAG(0),
+      None,
+      D3D11_SDK_VERSION,
+      Some(&mut device),
+      None,
+      Some(&mut device_context),
+    )?;
+    let device = device.unwrap();
+    let device_context = device_context.unwrap();
+
+    let mut contexts = Vec::new();
+    let mut output_index = 0;
+    while let Ok(output) = adapter.EnumOutputs(output_index) {
+      output_index += 1;
+
+      let output: IDXGIOutput1 = match output.cast() {
+        Ok(output) => output,
+        Err(_) => continue,
+      };
+      let output_duplication = match output.DuplicateOutput(&device) {
+        Ok(output_duplication) => output_duplication,
+        // Outputs that are disabled, or not driven by this adapter, fail here; skip them.
+        Err(_) => continue,
+      };
+
+      contexts.push(DuplicateContext::new(
+        device.clone(),
+        device_context.clone(),
+        output,
+        output_duplication,
+        DEFAULT_TIMEOUT_MS,
+      ));
+    }
+
+    Ok(contexts)
+  }
+}
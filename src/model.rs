@@ -0,0 +1,43 @@
+use std::fmt;
+
+/// Errors surfaced by the duplication pipeline.
+#[derive(Debug)]
+pub enum Error {
+  /// `AcquireNextFrame` hit its timeout with no new frame available. Not fatal: callers
+  /// should simply try again on the next loop iteration.
+  Timeout,
+  /// The desktop duplication session was lost (display mode switch, secure desktop
+  /// transition, fullscreen exclusive app, ...) and could not be re-established.
+  AccessLost,
+  /// A DXGI/D3D11 call failed.
+  Windows(windows::core::Error),
+  /// Anything else, e.g. a buffer that is too small.
+  Unexpected(String),
+}
+
+impl fmt::Display for Error {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Error::Timeout => write!(f, "timed out waiting for the next frame"),
+      Error::AccessLost => write!(f, "desktop duplication access was lost and could not be recovered"),
+      Error::Windows(e) => write!(f, "{e}"),
+      Error::Unexpected(message) => write!(f, "{message}"),
+    }
+  }
+}
+
+impl std::error::Error for Error {}
+
+impl From<&str> for Error {
+  fn from(message: &str) -> Self {
+    Error::Unexpected(message.to_string())
+  }
+}
+
+impl From<windows::core::Error> for Error {
+  fn from(e: windows::core::Error) -> Self {
+    Error::Windows(e)
+  }
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
@@ -1,8 +1,15 @@
-use windows::Win32::Graphics::Dxgi::{DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC};
+use windows::Win32::Foundation::POINT;
+use windows::Win32::Graphics::Dxgi::{
+  DXGI_MODE_ROTATION_ROTATE270, DXGI_MODE_ROTATION_ROTATE90, DXGI_OUTDUPL_FRAME_INFO,
+  DXGI_OUTDUPL_POINTER_SHAPE_INFO, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR,
+  DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME,
+  DXGI_OUTPUT_DESC,
+};
 
 pub trait OutputDescExt {
   fn width(&self) -> u32;
   fn height(&self) -> u32;
+  fn upright_dimensions(&self) -> (u32, u32);
   fn calc_buffer_size(&self) -> usize;
 }
 
@@ -14,9 +21,20 @@ impl OutputDescExt for DXGI_OUTPUT_DESC {
     (self.DesktopCoordinates.bottom - self.DesktopCoordinates.top) as u32
   }
 
-  /// Return needed buffer size, in bytes.
+  /// `(width, height)` of an upright (rotation-corrected) copy of the desktop image. The
+  /// duplicated GPU texture keeps the panel's native orientation, so for a 90/270-rotated
+  /// output this swaps [`Self::width`] and [`Self::height`].
+  fn upright_dimensions(&self) -> (u32, u32) {
+    match self.Rotation {
+      DXGI_MODE_ROTATION_ROTATE90 | DXGI_MODE_ROTATION_ROTATE270 => (self.height(), self.width()),
+      _ => (self.width(), self.height()),
+    }
+  }
+
+  /// Return needed buffer size, in bytes, for an upright copy of the desktop image.
   fn calc_buffer_size(&self) -> usize {
-    (self.width() * self.height() * 4) as usize // 4 for BGRA32
+    let (width, height) = self.upright_dimensions();
+    (width * height * 4) as usize // 4 for BGRA32
   }
 }
 
@@ -32,15 +50,92 @@ impl FrameInfoExt for DXGI_OUTDUPL_FRAME_INFO {
 
   /// Return true if mouse's shape or/and position is updated.
   fn mouse_updated(&self) -> bool {
-    self.LastMouseUpdateTime == 0
+    self.LastMouseUpdateTime != 0
+  }
+}
+
+/// Blend the hardware cursor into a BGRA32 `frame_buffer` (`frame_width` x `frame_height`),
+/// clipping the cursor rectangle to the frame bounds. `position` is the pointer's top-left
+/// corner in frame coordinates (`DXGI_OUTDUPL_FRAME_INFO.PointerPosition.Position`); `shape`
+/// describes the cached pointer shape and `shape_buffer` is its pixel data.
+pub fn composite_cursor(
+  frame_buffer: &mut [u8],
+  frame_width: u32,
+  frame_height: u32,
+  position: POINT,
+  shape: &DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+  shape_buffer: &[u8],
+) {
+  const BPP: usize = 4;
+  let frame_width = frame_width as i32;
+  let frame_height = frame_height as i32;
+  let pitch = shape.Pitch as usize;
+
+  let dest_pixel = |frame_buffer: &mut [u8], x: i32, y: i32| -> Option<&mut [u8]> {
+    if x < 0 || x >= frame_width || y < 0 || y >= frame_height {
+      return None;
+    }
+    let offset = (y as usize * frame_width as usize + x as usize) * BPP;
+    Some(&mut frame_buffer[offset..offset + BPP])
+  };
+
+  if shape.Type == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME.0 as u32 {
+    // height/2 AND-mask rows followed by height/2 XOR-mask rows, 1 bit per pixel.
+    let mask_height = shape.Height as i32 / 2;
+    for y in 0..mask_height {
+      for x in 0..shape.Width as i32 {
+        let Some(dest) = dest_pixel(frame_buffer, position.x + x, position.y + y) else {
+          continue;
+        };
+        let byte = x as usize / 8;
+        let bit = 7 - (x as usize % 8);
+        let and_bit = (shape_buffer[y as usize * pitch + byte] >> bit) & 1;
+        let xor_bit = (shape_buffer[(y + mask_height) as usize * pitch + byte] >> bit) & 1;
+        for channel in dest.iter_mut().take(3) {
+          *channel = (*channel & (and_bit.wrapping_neg())) ^ (0xFFu8 * xor_bit);
+        }
+      }
+    }
+    return;
+  }
+
+  // COLOR and MASKED_COLOR are both full BGRA rows, `Pitch` bytes wide.
+  for y in 0..shape.Height as i32 {
+    for x in 0..shape.Width as i32 {
+      let Some(dest) = dest_pixel(frame_buffer, position.x + x, position.y + y) else {
+        continue;
+      };
+      let src_offset = y as usize * pitch + x as usize * BPP;
+      let src = &shape_buffer[src_offset..src_offset + BPP];
+
+      if shape.Type == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32 {
+        let alpha = src[3] as u32;
+        for c in 0..3 {
+          dest[c] = (src[c] as u32 + dest[c] as u32 * (255 - alpha) / 255) as u8;
+        }
+      } else if shape.Type == DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MASKED_COLOR.0 as u32 {
+        if src[3] == 0xFF {
+          for c in 0..3 {
+            dest[c] ^= src[c];
+          }
+        } else {
+          dest[..3].copy_from_slice(&src[..3]);
+        }
+      }
+    }
   }
 }
 
 #[cfg(test)]
 mod tests {
-  use windows::Win32::Graphics::Dxgi::{DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTPUT_DESC};
+  use windows::Win32::Foundation::POINT;
+  use windows::Win32::Graphics::Dxgi::{
+    DXGI_MODE_ROTATION_ROTATE90, DXGI_OUTDUPL_FRAME_INFO, DXGI_OUTDUPL_POINTER_SHAPE_INFO,
+    DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR, DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME,
+    DXGI_OUTPUT_DESC,
+  };
 
-  use crate::utils::{FrameInfoExt, OutputDescExt};
+  use crate::utils::{composite_cursor, FrameInfoExt, OutputDescExt};
 
   #[test]
   fn output_desc_ext() {
@@ -54,11 +149,74 @@ mod tests {
     assert_eq!(desc.calc_buffer_size(), 1920 * 1080 * 4);
   }
 
+  #[test]
+  fn output_desc_ext_rotated_buffer_size() {
+    let mut desc = DXGI_OUTPUT_DESC::default();
+    desc.DesktopCoordinates.left = 0;
+    desc.DesktopCoordinates.top = 0;
+    desc.DesktopCoordinates.right = 1920;
+    desc.DesktopCoordinates.bottom = 1080;
+    // native texture stays 1920x1080 regardless of rotation
+    assert_eq!(desc.width(), 1920);
+    assert_eq!(desc.height(), 1080);
+    assert_eq!(desc.upright_dimensions(), (1920, 1080));
+
+    // but the upright buffer swaps width and height, which width*height*4 alone can't show
+    // since the product is the same either way
+    desc.Rotation = DXGI_MODE_ROTATION_ROTATE90;
+    assert_eq!(desc.upright_dimensions(), (1080, 1920));
+    assert_eq!(desc.calc_buffer_size(), 1080 * 1920 * 4);
+  }
+
   #[test]
   fn frame_info_ext() {
     let mut desc = DXGI_OUTDUPL_FRAME_INFO::default();
     assert!(!desc.desktop_updated());
     desc.LastPresentTime = 1;
     assert!(desc.desktop_updated());
+
+    assert!(!desc.mouse_updated());
+    desc.LastMouseUpdateTime = 1;
+    assert!(desc.mouse_updated());
+  }
+
+  #[test]
+  fn composite_cursor_color_blends_by_alpha() {
+    // 2x2 frame, all zeroed out, with a single fully-opaque COLOR cursor pixel placed at (1, 0).
+    let mut frame = vec![0u8; 2 * 2 * 4];
+    let shape = DXGI_OUTDUPL_POINTER_SHAPE_INFO {
+      Type: DXGI_OUTDUPL_POINTER_SHAPE_TYPE_COLOR.0 as u32,
+      Width: 1,
+      Height: 1,
+      Pitch: 4,
+      ..Default::default()
+    };
+    let shape_buffer = [10u8, 20, 30, 255]; // BGRA, fully opaque
+
+    composite_cursor(&mut frame, 2, 2, POINT { x: 1, y: 0 }, &shape, &shape_buffer);
+
+    // untouched pixels stay zero
+    assert_eq!(&frame[0..4], &[0, 0, 0, 0]);
+    // the cursor pixel takes the source color in B/G/R, alpha channel is left alone
+    assert_eq!(&frame[4..8], &[10, 20, 30, 0]);
+  }
+
+  #[test]
+  fn composite_cursor_monochrome_applies_and_xor_masks() {
+    // 1x1 frame and a 1x2 shape: one AND-mask row (all one bits -> keep the pixel) followed by one XOR-mask
+    // row (all one bits -> invert it), 1 bit per pixel, byte-aligned pitch.
+    let mut frame = vec![100u8, 110, 120, 200];
+    let shape = DXGI_OUTDUPL_POINTER_SHAPE_INFO {
+      Type: DXGI_OUTDUPL_POINTER_SHAPE_TYPE_MONOCHROME.0 as u32,
+      Width: 1,
+      Height: 2,
+      Pitch: 1,
+      ..Default::default()
+    };
+    let shape_buffer = [0b1000_0000, 0b1000_0000]; // AND row, then XOR row
+
+    composite_cursor(&mut frame, 1, 1, POINT { x: 0, y: 0 }, &shape, &shape_buffer);
+
+    assert_eq!(&frame, &[155, 145, 135, 200]); // B/G/R inverted, alpha untouched
   }
 }